@@ -0,0 +1,243 @@
+/*!
+Defines the "low level" representation of CLI arguments.
+
+These are the arguments more or less exactly as they come off the command
+line, before any of the validation or derived-state computation that
+happens when they're converted into `HiArgs` (see `crate::hiargs`). Flags
+in `crate::flags::defs` mutate a `LowArgs` in place as they're parsed.
+*/
+
+use std::path::PathBuf;
+
+use bstr::BString;
+
+/// The low level representation of every CLI argument ripgrep accepts.
+///
+/// This is deliberately a plain old bag of fields: each flag in
+/// `crate::flags::defs` knows how to update exactly the fields it owns,
+/// and `HiArgs::from_low_args` is the only place that reads them back out
+/// and turns them into something more structured.
+#[derive(Debug, Default)]
+pub(crate) struct LowArgs {
+    pub(crate) backup_suffix: Option<BString>,
+    pub(crate) boundary: Option<BoundaryMode>,
+    pub(crate) buffer: BufferMode,
+    pub(crate) byte_offset: bool,
+    pub(crate) case: CaseMode,
+    pub(crate) color: ColorChoice,
+    pub(crate) column: Option<bool>,
+    pub(crate) context: ContextMode,
+    pub(crate) context_separator: ContextSeparator,
+    pub(crate) crlf: bool,
+    pub(crate) dfa_size_limit: Option<usize>,
+    pub(crate) encoding: EncodingMode,
+    pub(crate) engine: EngineChoice,
+    pub(crate) field_context_separator: FieldContextSeparator,
+    pub(crate) field_match_separator: FieldMatchSeparator,
+    pub(crate) fixed_strings: bool,
+    pub(crate) follow: bool,
+    pub(crate) heading: Option<bool>,
+    pub(crate) hidden: bool,
+    pub(crate) ignore_file: Vec<PathBuf>,
+    pub(crate) ignore_file_case_insensitive: bool,
+    pub(crate) include_zero: bool,
+    pub(crate) invert_match: bool,
+    pub(crate) line_number: Option<bool>,
+    pub(crate) line_number_width: Option<usize>,
+    pub(crate) max_columns: Option<u64>,
+    pub(crate) max_columns_preview: bool,
+    pub(crate) max_count: Option<u64>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_filesize: Option<u64>,
+    pub(crate) mmap: MmapMode,
+    pub(crate) mmap_min_size: Option<u64>,
+    pub(crate) mode: Mode,
+    pub(crate) multiline: bool,
+    pub(crate) multiline_dotall: bool,
+    pub(crate) no_ignore_dot: bool,
+    pub(crate) no_ignore_exclude: bool,
+    pub(crate) no_ignore_files: bool,
+    pub(crate) no_ignore_global: bool,
+    pub(crate) no_ignore_parent: bool,
+    pub(crate) no_ignore_vcs: bool,
+    pub(crate) no_require_git: bool,
+    pub(crate) no_unicode: bool,
+    pub(crate) null: bool,
+    pub(crate) null_data: bool,
+    pub(crate) only_matching: bool,
+    pub(crate) pattern_source: Option<PatternSource>,
+    pub(crate) quiet: bool,
+    pub(crate) regex_size_limit: Option<usize>,
+    pub(crate) replace: Option<BString>,
+    pub(crate) search_zip: bool,
+    pub(crate) sort: Option<SortMode>,
+    pub(crate) sort_spill_size: Option<usize>,
+    pub(crate) special: Option<()>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) type_changes: Vec<TypeChange>,
+    pub(crate) vimgrep: bool,
+    pub(crate) with_filename: Option<bool>,
+}
+
+/// Where patterns come from: `-e`/`-f`, or the first positional argument.
+#[derive(Clone, Debug)]
+pub(crate) enum PatternSource {
+    Args(Vec<BString>),
+    File(Vec<PathBuf>),
+}
+
+/// The overall mode ripgrep runs in.
+///
+/// `Search` covers every mode that still goes through the normal
+/// find-matches-and-print pipeline (see `SearchMode` for which kind of
+/// output that produces). `ReplaceInPlace` is its own top-level variant,
+/// per `--replace-in-place`: it still searches files the same way, but
+/// instead of printing matches it rewrites the searched file on disk, so
+/// it doesn't make sense to also pick a `SearchMode`.
+#[derive(Clone, Debug)]
+pub(crate) enum Mode {
+    Search(SearchMode),
+    ReplaceInPlace,
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::Search(SearchMode::default())
+    }
+}
+
+/// Which shape of output a `Mode::Search` run produces.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum SearchMode {
+    #[default]
+    Standard,
+    Count,
+    CountMatches,
+    FilesWithMatches,
+    FilesWithoutMatch,
+    JSON,
+}
+
+/// How `--sort`/`--sortr` should order results.
+#[derive(Clone, Debug)]
+pub(crate) struct SortMode {
+    pub(crate) kind: SortModeKind,
+    pub(crate) reverse: bool,
+}
+
+impl SortMode {
+    /// Whether this platform/build supports sorting by this mode's key.
+    ///
+    /// For example, not every filesystem reports a creation time, so
+    /// `SortModeKind::Created` can fail here well before we've started
+    /// walking anything.
+    pub(crate) fn supported(&self) -> anyhow::Result<()> {
+        if self.kind == SortModeKind::Created {
+            let cwd = std::env::current_dir()?;
+            if let Err(err) = cwd.metadata()?.created() {
+                anyhow::bail!(
+                    "sorting by creation time is not supported \
+                     on this platform/filesystem: {err}"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The key used to order search results for `--sort`/`--sortr`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SortModeKind {
+    Path,
+    LastModified,
+    LastAccessed,
+    Created,
+    FileSize,
+}
+
+/// Whether memory maps are used to search files.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum MmapMode {
+    #[default]
+    Auto,
+    AlwaysTryMmap,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum BinaryMode {
+    #[default]
+    Auto,
+    Binary,
+    Text,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum BoundaryMode {
+    Line,
+    Word,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum BufferMode {
+    #[default]
+    Auto,
+    Line,
+    Block,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    Smart,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+    Ansi,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) enum ContextMode {
+    #[default]
+    Passthru,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ContextSeparator(Option<BString>);
+
+impl ContextSeparator {
+    pub(crate) fn into_bytes(self) -> Option<Vec<u8>> {
+        self.0.map(|b| b.into())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum EncodingMode {
+    #[default]
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum EngineChoice {
+    #[default]
+    Default,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldContextSeparator(BString);
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldMatchSeparator(BString);
+
+#[derive(Clone, Debug)]
+pub(crate) enum TypeChange {
+    Add(String),
+    Clear(String),
+}
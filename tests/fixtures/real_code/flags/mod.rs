@@ -0,0 +1,44 @@
+/*!
+CLI flag definitions and the low-level arguments they populate.
+*/
+
+pub(crate) mod lowargs;
+
+use self::lowargs::LowArgs;
+
+/// A single CLI flag.
+///
+/// Each flag knows its own name(s), its documentation, and how to fold a
+/// value (or, for switches, its mere presence) into a `LowArgs`. Parsing
+/// itself (turning `std::env::args_os()` into `(&'static dyn Flag,
+/// Option<OsString>)` pairs) lives elsewhere; this trait is only
+/// responsible for what happens once a flag has been matched.
+pub(crate) trait Flag: Send + Sync + 'static {
+    /// The flag's long name, e.g. `"line-number-width"` for
+    /// `--line-number-width`.
+    fn name_long(&self) -> &'static str;
+
+    /// Whether this flag takes a value (`--foo=BAR`) or is a plain switch
+    /// (`--foo`).
+    fn is_switch(&self) -> bool {
+        false
+    }
+
+    /// A one-line summary shown in `-h`.
+    fn doc_short(&self) -> &'static str;
+
+    /// The full description shown in `--help`.
+    fn doc_long(&self) -> &'static str {
+        self.doc_short()
+    }
+
+    /// Fold this flag's value into `args`.
+    ///
+    /// `value` is `None` for switches and for negated flags; it's
+    /// `Some(..)` whenever the flag was given a `=VALUE`.
+    fn update(
+        &self,
+        value: Option<&std::ffi::OsStr>,
+        args: &mut LowArgs,
+    ) -> anyhow::Result<()>;
+}
@@ -0,0 +1,43 @@
+/*!
+A `Haystack` is a single thing ripgrep searches: almost always a file
+path, but occasionally stdin.
+*/
+
+use std::path::{Path, PathBuf};
+
+/// A single thing to search.
+#[derive(Clone, Debug)]
+pub(crate) struct Haystack {
+    path: PathBuf,
+    is_stdin: bool,
+}
+
+impl Haystack {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn is_stdin(&self) -> bool {
+        self.is_stdin
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HaystackBuilder {
+    is_stdin: bool,
+}
+
+impl HaystackBuilder {
+    pub(crate) fn new() -> HaystackBuilder {
+        HaystackBuilder::default()
+    }
+
+    pub(crate) fn stdin(mut self) -> Haystack {
+        self.is_stdin = true;
+        Haystack { path: PathBuf::from("-"), is_stdin: true }
+    }
+
+    pub(crate) fn build(&self, path: PathBuf) -> Haystack {
+        Haystack { path, is_stdin: false }
+    }
+}
@@ -34,6 +34,7 @@ use crate::{
 /// level arguments aren't created until parsing has completely finished.
 #[derive(Debug)]
 pub(crate) struct HiArgs {
+    backup_suffix: Option<BString>,
     binary: BinaryDetection,
     boundary: Option<BoundaryMode>,
     buffer: BufferMode,
@@ -64,12 +65,14 @@ pub(crate) struct HiArgs {
     invert_match: bool,
     is_terminal_stdout: bool,
     line_number: bool,
+    line_number_width: Option<usize>,
     max_columns: Option<u64>,
     max_columns_preview: bool,
     max_count: Option<u64>,
     max_depth: Option<usize>,
     max_filesize: Option<u64>,
-    mmap_choice: grep::searcher::MmapChoice,
+    mmap: MmapMode,
+    mmap_min_size: u64,
     mode: Mode,
     multiline: bool,
     multiline_dotall: bool,
@@ -96,6 +99,7 @@ pub(crate) struct HiArgs {
     replace: Option<BString>,
     search_zip: bool,
     sort: Option<SortMode>,
+    sort_spill_size: usize,
     stats: Option<grep::printer::Stats>,
     stop_on_nonmatch: bool,
     threads: usize,
@@ -105,6 +109,21 @@ pub(crate) struct HiArgs {
     with_filename: bool,
 }
 
+/// The default value for `--mmap-min-size` when the flag isn't given.
+///
+/// Below this size, the overhead of setting up a memory map tends to
+/// outweigh whatever benefit it gives over a plain buffered read, so we
+/// default to only bothering with mmap for files at or above this size.
+const DEFAULT_MMAP_MIN_SIZE: u64 = 16 * 1024; // 16 KiB
+
+/// The default value for `--sort-spill-size` when the flag isn't given.
+///
+/// This is the threshold `sort::Buffer` used back when it was a
+/// hardcoded constant; keeping it as the default means not passing
+/// `--sort-spill-size` behaves exactly like before it became
+/// configurable.
+const DEFAULT_SORT_SPILL_SIZE: usize = 1 << 20; // 1 MiB
+
 impl HiArgs {
     /// Convert low level arguments into high level arguments.
     ///
@@ -143,6 +162,17 @@ impl HiArgs {
         let patterns = Patterns::from_low_args(&mut state, &mut low)?;
         let paths = Paths::from_low_args(&mut state, &patterns, &mut low)?;
 
+        // `--replace-in-place` rewrites files on disk, which doesn't make
+        // sense when (part of) the haystack is stdin: there's no file to
+        // rewrite and no safe place to put the atomic-rename temp file.
+        if matches!(low.mode, Mode::ReplaceInPlace)
+            && paths.paths.iter().any(|p| p == Path::new("-"))
+        {
+            anyhow::bail!(
+                "--replace-in-place cannot be used when searching stdin"
+            );
+        }
+
         let binary = BinaryDetection::from_low_args(&state, &low);
         let colors = take_color_specs(&mut state, &mut low);
         let hyperlink_config = take_hyperlink_config(&mut state, &mut low)?;
@@ -165,7 +195,16 @@ impl HiArgs {
         };
         let path_terminator = if low.null { Some(b'\x00') } else { None };
         let quit_after_match = stats.is_none() && low.quiet;
-        let threads = if low.sort.is_some() || paths.is_one_file {
+        // `low.sort` no longer has any bearing on the thread count: see
+        // the `sort` module docs for why sorting and concurrency are
+        // independent these days.
+        let threads = if paths.is_one_file
+            || matches!(low.mode, Mode::ReplaceInPlace)
+        {
+            // Each file is only ever touched by one worker already, but for
+            // `--replace-in-place` we additionally pin the whole search to a
+            // single thread for now, since rewriting files on disk is a lot
+            // less forgiving of surprises than printing to stdout.
             1
         } else if let Some(threads) = low.threads {
             threads
@@ -218,40 +257,36 @@ impl HiArgs {
                 }
             }
         });
-
-        let mmap_choice = {
-            // SAFETY: Memory maps are difficult to impossible to encapsulate
-            // safely in a portable way that doesn't simultaneously negate some
-            // of the benfits of using memory maps. For ripgrep's use, we never
-            // mutate a memory map and generally never store the contents of
-            // memory map in a data structure that depends on immutability.
-            // Generally speaking, the worst thing that can happen is a SIGBUS
-            // (if the underlying file is truncated while reading it), which
-            // will cause ripgrep to abort. This reasoning should be treated as
-            // suspect.
-            let maybe = unsafe { grep::searcher::MmapChoice::auto() };
-            let never = grep::searcher::MmapChoice::never();
-            match low.mmap {
-                MmapMode::Auto => {
-                    if paths.paths.len() <= 10
-                        && paths.paths.iter().all(|p| p.is_file())
-                    {
-                        // If we're only searching a few paths and all of them
-                        // are files, then memory maps are probably faster.
-                        maybe
-                    } else {
-                        never
-                    }
-                }
-                MmapMode::AlwaysTryMmap => maybe,
-                MmapMode::Never => never,
-            }
+        // Only meaningful when we're actually printing line numbers, and
+        // `vimgrep` output is consumed by machines that split on `:`, so a
+        // padded column would just be noise there.
+        let line_number_width = if line_number && !low.vimgrep {
+            low.line_number_width
+        } else {
+            None
         };
 
+        let sort_spill_size =
+            low.sort_spill_size.unwrap_or(DEFAULT_SORT_SPILL_SIZE);
+
+        let mmap_min_size =
+            low.mmap_min_size.unwrap_or(DEFAULT_MMAP_MIN_SIZE);
+        // Unlike most of what's computed above, whether to mmap a given
+        // file can't be decided once here for the whole run: `--mmap`'s
+        // `MmapMode::Auto` needs to weigh each candidate's own size
+        // against `mmap_min_size`, and candidates keep showing up
+        // throughout the directory walk long after this function
+        // returns. So we only store `mmap`/`mmap_min_size` on `HiArgs`
+        // and leave the actual per-path choice to `mmap::mmap_choice`,
+        // called once per file right before searching it -- which also
+        // means it naturally applies to every file the walk finds, not
+        // just the paths given on the command line.
+
         Ok(HiArgs {
             mode: low.mode,
             patterns,
             paths,
+            backup_suffix: low.backup_suffix,
             binary,
             boundary: low.boundary,
             buffer: low.buffer,
@@ -281,12 +316,14 @@ impl HiArgs {
             invert_match: low.invert_match,
             is_terminal_stdout: state.is_terminal_stdout,
             line_number,
+            line_number_width,
             max_columns: low.max_columns,
             max_columns_preview: low.max_columns_preview,
             max_count: low.max_count,
             max_depth: low.max_depth,
             max_filesize: low.max_filesize,
-            mmap_choice,
+            mmap: low.mmap,
+            mmap_min_size,
             multiline: low.multiline,
             multiline_dotall: low.multiline_dotall,
             no_ignore_dot: low.no_ignore_dot,
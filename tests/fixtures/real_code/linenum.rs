@@ -0,0 +1,219 @@
+/*!
+Padding of the line number column for `--line-number-width`.
+*/
+
+use std::io::{self, Write};
+
+/// Wraps a printer's output writer and pads the decimal line number at
+/// the start of every line out to a fixed width with spaces on its left
+/// (right-aligning it), per `--line-number-width=N`.
+///
+/// `grep::printer::Standard` writes each printed line as a run of ASCII
+/// digits (the line number) followed by a field separator (`:` for a
+/// matched line, `-` for a context line) and then the line's text. This
+/// wrapper looks for exactly that shape at the start of each line it
+/// sees and pads the digit run; anything else (a line with no leading
+/// digit run, e.g. because line numbers are off) is passed through
+/// unchanged.
+///
+/// Ripgrep's default `--color auto` colors the line number and
+/// separator whenever stdout is a tty -- precisely the interactive case
+/// this flag targets -- by wrapping each in its own ANSI escape
+/// sequence. `ansi_escape_len` skips those so the digit run and
+/// separator are still found underneath the color codes, rather than
+/// this silently becoming a no-op as soon as output is colorized.
+///
+/// Constructing one with `width: None` makes it a transparent
+/// passthrough. `Printer::standard` only actually reaches for this
+/// wrapper when `--line-number-width` was given; otherwise it builds the
+/// standard printer directly over `W`, so the common case never pays for
+/// this type's per-line buffering at all.
+pub(crate) struct LineNumberPad<W> {
+    wtr: W,
+    width: Option<usize>,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> LineNumberPad<W> {
+    pub(crate) fn new(wtr: W, width: Option<usize>) -> LineNumberPad<W> {
+        LineNumberPad { wtr, width, pending: Vec::new() }
+    }
+
+    /// Pad the leading digit run of `line` (including its trailing
+    /// newline, if any) out to `width` and write it to `self.wtr`.
+    ///
+    /// The digit run and the separator after it are located by skipping
+    /// over any ANSI escape sequence found immediately before each, so
+    /// this still finds them (and pads correctly) when `--color` has
+    /// wrapped the number and/or separator in color codes.
+    fn write_padded_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let Some(width) = self.width else {
+            return self.wtr.write_all(line);
+        };
+        let digit_start = skip_escapes(line);
+        let digits = line[digit_start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        let after_digits = digit_start + digits;
+        let separator_start =
+            after_digits + skip_escapes(&line[after_digits..]);
+        let has_separator =
+            matches!(line.get(separator_start), Some(b':') | Some(b'-'));
+        if digits == 0 || !has_separator {
+            return self.wtr.write_all(line);
+        }
+        for _ in digits..width {
+            self.wtr.write_all(b" ")?;
+        }
+        self.wtr.write_all(line)
+    }
+}
+
+/// If `bytes` starts with an ANSI CSI escape sequence (as `termcolor`,
+/// which `grep::printer::Standard` colorizes through, emits), returns
+/// that sequence's length in bytes. Otherwise `0`.
+///
+/// Recognizes `ESC '[' <parameter/intermediate bytes> <final byte>`,
+/// which covers the SGR (`m`-terminated) sequences used to color the
+/// line number and separator -- the only kind seen here.
+fn ansi_escape_len(bytes: &[u8]) -> usize {
+    if bytes.first() != Some(&0x1B) || bytes.get(1) != Some(&b'[') {
+        return 0;
+    }
+    let mut len = 2;
+    while let Some(&b) = bytes.get(len) {
+        len += 1;
+        if matches!(b, 0x40..=0x7E) {
+            return len;
+        }
+    }
+    0
+}
+
+/// Returns the length in bytes of every ANSI escape sequence found back
+/// to back at the start of `bytes`. There can be more than one right
+/// before the separator: the number's own color-reset sequence followed
+/// immediately by the separator's color-on sequence.
+fn skip_escapes(bytes: &[u8]) -> usize {
+    let mut total = 0;
+    loop {
+        let len = ansi_escape_len(&bytes[total..]);
+        if len == 0 {
+            return total;
+        }
+        total += len;
+    }
+}
+
+impl<W: Write> Write for LineNumberPad<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(pos) =
+            self.pending.iter().position(|&b| b == b'\n')
+        {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.write_padded_line(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.write_padded_line(&line)?;
+        }
+        self.wtr.flush()
+    }
+}
+
+impl<W: Write> Drop for LineNumberPad<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(width: Option<usize>, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        {
+            let mut w = LineNumberPad::new(&mut out, width);
+            w.write_all(input).unwrap();
+            w.flush().unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn pads_matched_and_context_lines() {
+        let out = pad(Some(4), b"3:foo\n10-bar\n");
+        assert_eq!(out, b"   3:foo\n  10-bar\n");
+    }
+
+    #[test]
+    fn leaves_wider_numbers_alone() {
+        let out = pad(Some(2), b"12345:foo\n");
+        assert_eq!(out, b"12345:foo\n");
+    }
+
+    #[test]
+    fn none_width_is_a_passthrough() {
+        let out = pad(None, b"3:foo\n10-bar\n");
+        assert_eq!(out, b"3:foo\n10-bar\n");
+    }
+
+    #[test]
+    fn lines_without_a_number_prefix_are_untouched() {
+        let out = pad(Some(4), b"--\nsome text with no prefix\n");
+        assert_eq!(out, b"--\nsome text with no prefix\n");
+    }
+
+    #[test]
+    fn handles_writes_split_across_calls() {
+        let mut out = Vec::new();
+        {
+            let mut w = LineNumberPad::new(&mut out, Some(3));
+            w.write_all(b"5").unwrap();
+            w.write_all(b":fo").unwrap();
+            w.write_all(b"o\n").unwrap();
+            w.flush().unwrap();
+        }
+        assert_eq!(out, b"  5:foo\n");
+    }
+
+    #[test]
+    fn flushes_a_trailing_line_with_no_newline() {
+        let out = pad(Some(3), b"5:no newline");
+        assert_eq!(out, b"  5:no newline");
+    }
+
+    #[test]
+    fn pads_through_color_codes_around_the_number() {
+        // `--color` wraps the colored line number and separator each in
+        // their own SGR escape sequence, e.g.
+        // `<on>3<off><on>:<off>foo`; padding must still find the digit
+        // run and separator underneath those.
+        let out = pad(Some(4), b"\x1b[32m3\x1b[0m\x1b[36m:\x1b[0mfoo\n");
+        assert_eq!(
+            out,
+            b"   \x1b[32m3\x1b[0m\x1b[36m:\x1b[0mfoo\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn pads_through_a_single_color_code_around_the_number() {
+        let out = pad(Some(4), b"\x1b[32m3\x1b[0m:foo\n");
+        assert_eq!(out, b"   \x1b[32m3\x1b[0m:foo\n".to_vec());
+    }
+
+    #[test]
+    fn ansi_escape_len_recognizes_sgr_sequences_only() {
+        assert_eq!(ansi_escape_len(b"\x1b[32m3"), 5);
+        assert_eq!(ansi_escape_len(b"\x1b[0m"), 4);
+        assert_eq!(ansi_escape_len(b"3:foo"), 0);
+        assert_eq!(ansi_escape_len(b"\x1b[32"), 0);
+    }
+}
@@ -0,0 +1,97 @@
+/*!
+Per-candidate decision of whether to memory-map a file, for
+`--mmap`/`--mmap-min-size`.
+*/
+
+use std::path::Path;
+
+use crate::flags::lowargs::MmapMode;
+
+/// Decide whether to try memory-mapping `path`.
+///
+/// The old heuristic computed a single choice up front for the whole
+/// run: `MmapMode::Auto` tried mmap only if *every* explicitly-given CLI
+/// path was a regular file (later, only if every one of them also
+/// cleared `mmap_min_size`). That meant one small file among the
+/// arguments disabled mmap for every other -- possibly huge -- candidate
+/// in the same invocation, and it never looked at files a directory
+/// walk turned up at all, since those aren't in `paths.paths`.
+///
+/// Call this once per file instead, right before searching it (see
+/// `HiArgs::from_low_args`, which stores `mmap`/`mmap_min_size` for
+/// exactly this), so every candidate -- CLI argument or walked file
+/// alike -- gets its own answer based on its own size.
+pub(crate) fn mmap_choice(
+    mode: MmapMode,
+    mmap_min_size: u64,
+    path: &Path,
+) -> grep::searcher::MmapChoice {
+    // SAFETY: Memory maps are difficult to impossible to encapsulate
+    // safely in a portable way that doesn't simultaneously negate some of
+    // the benefits of using memory maps. For ripgrep's use, we never
+    // mutate a memory map and generally never store the contents of a
+    // memory map in a data structure that depends on immutability.
+    // Generally speaking, the worst thing that can happen is a SIGBUS (if
+    // the underlying file is truncated while reading it), which will
+    // cause ripgrep to abort. This reasoning should be treated as
+    // suspect.
+    let maybe = unsafe { grep::searcher::MmapChoice::auto() };
+    let never = grep::searcher::MmapChoice::never();
+    match mode {
+        MmapMode::Auto => {
+            if big_enough(path, mmap_min_size) { maybe } else { never }
+        }
+        MmapMode::AlwaysTryMmap => maybe,
+        MmapMode::Never => never,
+    }
+}
+
+/// Whether `path` clears `mmap_min_size` for `MmapMode::Auto`: it must
+/// stat as a regular file at or above the threshold.
+fn big_enough(path: &Path, mmap_min_size: u64) -> bool {
+    path.metadata()
+        .map(|md| md.is_file() && md.len() >= mmap_min_size)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp_file(name: &str, size: u64) -> std::path::PathBuf {
+        let path = crate::tempname::unique_path(
+            &std::env::temp_dir(),
+            &format!("mmap-test-{name}"),
+        );
+        std::fs::write(&path, vec![b'x'; size as usize]).unwrap();
+        path
+    }
+
+    #[test]
+    fn big_enough_respects_threshold() {
+        let small = write_temp_file("small", 10);
+        let big = write_temp_file("big", 100);
+        assert!(!big_enough(&small, 50));
+        assert!(big_enough(&big, 50));
+        std::fs::remove_file(&small).unwrap();
+        std::fs::remove_file(&big).unwrap();
+    }
+
+    #[test]
+    fn big_enough_rejects_directories() {
+        let dir = std::env::temp_dir();
+        assert!(!big_enough(&dir, 0));
+    }
+
+    #[test]
+    fn big_enough_is_decided_per_path_not_across_all_of_them() {
+        // A single small candidate must not affect the verdict for a
+        // separate, large one in the same invocation.
+        let small = write_temp_file("mixed-small", 10);
+        let big = write_temp_file("mixed-big", 100);
+        assert!(!big_enough(&small, 50));
+        assert!(big_enough(&big, 50));
+        std::fs::remove_file(&small).unwrap();
+        std::fs::remove_file(&big).unwrap();
+    }
+}
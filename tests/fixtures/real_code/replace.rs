@@ -0,0 +1,449 @@
+/*!
+Implements `--replace-in-place`: rewrite each matched file on disk with
+its replacements applied, instead of printing matches.
+
+The new contents are streamed to a temp file created alongside the
+original (so the final rename stays on the same filesystem and is
+atomic), fsync'd, and then renamed over the original. This means a crash
+or a concurrent reader only ever sees the old file or the fully-written
+new one, never a half-written file. `--backup-suffix` additionally
+copies the original aside before the rename. Files are handled per the
+existing `BinaryDetection` settings, same as a normal search would:
+`Quit` skips the file entirely, while `Convert` searches a copy of the
+file with the detection byte converted to a line terminator first --
+but only to find line boundaries and matches. The bytes actually
+written back always come from the original, unconverted file, so a
+detection byte anywhere outside a match is never touched on disk.
+*/
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use grep::{
+    matcher::{Captures, Matcher},
+    searcher::BinaryDetection,
+};
+
+/// How many leading bytes of a file to inspect when deciding whether it
+/// looks binary, mirroring the normal search path's own detection
+/// window.
+const BINARY_DETECTION_WINDOW: usize = 64 * 1024;
+
+/// Rewrites files in place for `--replace-in-place`.
+pub(crate) struct InPlaceReplacer {
+    backup_suffix: Option<Vec<u8>>,
+    binary: BinaryDetection,
+}
+
+impl InPlaceReplacer {
+    pub(crate) fn new(
+        backup_suffix: Option<Vec<u8>>,
+        binary: BinaryDetection,
+    ) -> InPlaceReplacer {
+        InPlaceReplacer { backup_suffix, binary }
+    }
+
+    /// Apply `matcher`'s matches to `path`'s contents, replacing each
+    /// with `replacement`, and atomically rewrite `path` with the
+    /// result.
+    ///
+    /// Returns `Ok(false)` without touching `path` if it looks binary
+    /// per `self.binary`, or if the matcher never actually matched
+    /// anything in it -- there's no point rewriting, fsyncing and
+    /// (optionally) backing up a file byte-for-identical to what's
+    /// already there. `path` must not be stdin; callers are expected
+    /// to have already rejected that case (see
+    /// `HiArgs::from_low_args`), since there's no file here to rewrite
+    /// and no safe place to put the temp file.
+    pub(crate) fn replace_file<M: Matcher>(
+        &self,
+        path: &Path,
+        matcher: &M,
+        replacement: &[u8],
+    ) -> anyhow::Result<bool> {
+        let contents = fs::read(path)?;
+        if looks_binary(&self.binary, &contents) {
+            return Ok(false);
+        }
+
+        // `search_contents` is only ever used to find line boundaries and
+        // matches: when the detection byte has been converted to `\n` it
+        // can split a line differently than the original bytes would, but
+        // the conversion never changes the file's length, so an offset
+        // into `search_contents` is always a valid offset into `contents`
+        // too. `replace_line` uses that to read the bytes it actually
+        // writes -- everything outside a match -- from `contents`, so a
+        // detection byte untouched by a match is never altered on disk.
+        let converted;
+        let search_contents: &[u8] =
+            if let BinaryDetection::Convert(byte) = self.binary {
+                converted = {
+                    let mut c = contents.clone();
+                    convert_binary_bytes(&mut c, byte);
+                    c
+                };
+                &converted
+            } else {
+                &contents
+            };
+
+        let mut changed = false;
+        let mut new_contents = Vec::with_capacity(contents.len());
+        let mut offset = 0;
+        for search_line in search_contents.split_inclusive(|&b| b == b'\n') {
+            let line = &contents[offset..offset + search_line.len()];
+            offset += search_line.len();
+            let replaced =
+                replace_line(matcher, line, search_line, replacement)?;
+            if replaced != line {
+                changed = true;
+            }
+            new_contents.extend_from_slice(&replaced);
+        }
+        if !changed {
+            return Ok(false);
+        }
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut tmp = TempFile::create_in(dir)?;
+        if let Err(err) = self.finish_replace(path, &mut tmp, &new_contents) {
+            // None of `write_all`/`sync_all`/`set_permissions`/the backup
+            // `fs::copy`/the final `rename` left `path` itself touched,
+            // but they can each fail after `tmp` was already created on
+            // disk; without this, a failure partway through would leave
+            // an orphaned `.rg-replace-<pid>-<id>` next to `path` forever.
+            let _ = fs::remove_file(&tmp.path);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// The rest of `replace_file` once `tmp` has been created: write the
+    /// new contents, sync them, carry over `path`'s permissions, take the
+    /// optional backup, and rename `tmp` over `path`. Split out so
+    /// `replace_file` can clean up `tmp` on any error this returns.
+    fn finish_replace(
+        &self,
+        path: &Path,
+        tmp: &mut TempFile,
+        new_contents: &[u8],
+    ) -> anyhow::Result<()> {
+        tmp.file.write_all(new_contents)?;
+        tmp.file.sync_all()?;
+
+        let perms = fs::metadata(path)?.permissions();
+        fs::set_permissions(&tmp.path, perms)?;
+
+        if let Some(suffix) = &self.backup_suffix {
+            let mut backup_name = path.as_os_str().to_owned();
+            backup_name.push(bytes_to_os_str(suffix));
+            fs::copy(path, Path::new(&backup_name))?;
+        }
+
+        fs::rename(&tmp.path, path)?;
+        Ok(())
+    }
+}
+
+/// Replace every match `matcher` finds in `search_line` with
+/// `replacement`, leaving everything else untouched. `replacement` is
+/// interpolated the same way the printed `--replace` output is, so
+/// `$1`/`${name}`-style references to `matcher`'s capture groups work
+/// here too.
+///
+/// `search_line` and `line` must be the same length and differ only in
+/// which bytes are a detection byte vs. its converted line terminator
+/// (see `InPlaceReplacer::replace_file`): matches and capture groups are
+/// found in `search_line`, but all of the bytes actually written to the
+/// result -- both the unmatched spans and any capture group text pulled
+/// into `replacement` -- are read from `line`.
+fn replace_line<M: Matcher>(
+    matcher: &M,
+    line: &[u8],
+    search_line: &[u8],
+    replacement: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let mut caps = matcher
+        .new_captures()
+        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    let mut out = Vec::with_capacity(line.len());
+    let mut last_end = 0;
+    matcher
+        .captures_iter(search_line, &mut caps, |caps| {
+            let m = caps.get(0).expect("captures_iter always sets capture 0");
+            out.extend_from_slice(&line[last_end..m.start()]);
+            caps.interpolate(
+                |name| matcher.capture_index(name),
+                line,
+                replacement,
+                &mut out,
+            );
+            last_end = m.end();
+            true
+        })
+        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    out.extend_from_slice(&line[last_end..]);
+    Ok(out)
+}
+
+/// The same heuristic a normal search uses to decide a file is binary and
+/// give up on it entirely: look for the configured "quit" byte (typically
+/// NUL) within the first `BINARY_DETECTION_WINDOW` bytes.
+///
+/// `BinaryDetection::Convert` is deliberately *not* treated as "skip"
+/// here: unlike `Quit`, it doesn't mean the real search path abandons the
+/// file, it means the file is searched with the detection byte converted
+/// to a line terminator first (see `convert_binary_bytes`), same as a
+/// normal search over it would.
+fn looks_binary(binary: &BinaryDetection, contents: &[u8]) -> bool {
+    let quit_byte = match binary {
+        BinaryDetection::Quit(byte) => *byte,
+        _ => return false,
+    };
+    contents[..contents.len().min(BINARY_DETECTION_WINDOW)]
+        .contains(&quit_byte)
+}
+
+/// Mirror the real search path's `BinaryDetection::Convert` behavior:
+/// replace every occurrence of `byte` with the line terminator so lines
+/// split the same way a normal search over this file would see them,
+/// instead of one huge line run together by whatever binary data was in
+/// the way.
+fn convert_binary_bytes(contents: &mut [u8], byte: u8) {
+    for b in contents {
+        if *b == byte {
+            *b = b'\n';
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_os_str(bytes: &[u8]) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).to_owned()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_str(bytes: &[u8]) -> std::ffi::OsString {
+    // Non-UTF-8 backup suffixes can't be represented as a `Path` on
+    // platforms without an `OsStrExt` like this, so fall back to a lossy
+    // conversion rather than failing the whole replace.
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// A temp file created alongside the file being replaced, so the final
+/// `fs::rename` stays on the same filesystem and is therefore atomic.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    /// Opened with `create_new`, per `tempname::unique_path`'s contract,
+    /// so a pre-existing file at the chosen path doesn't get silently
+    /// opened through. On `AlreadyExists` we just ask for another name
+    /// and try again, same as `sort::spill_file`.
+    fn create_in(dir: &Path) -> io::Result<TempFile> {
+        loop {
+            let path = crate::tempname::unique_path(dir, "replace");
+            let opened =
+                File::options().write(true).create_new(true).open(&path);
+            match opened {
+                Ok(file) => return Ok(TempFile { path, file }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    continue
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grep::regex::RegexMatcher;
+
+    #[test]
+    fn looks_binary_detects_quit_byte_in_window() {
+        let binary = BinaryDetection::Quit(0);
+        assert!(looks_binary(&binary, b"hello\0world"));
+        assert!(!looks_binary(&binary, b"hello world"));
+    }
+
+    #[test]
+    fn looks_binary_none_never_skips() {
+        assert!(!looks_binary(&BinaryDetection::None, b"hello\0world"));
+    }
+
+    #[test]
+    fn looks_binary_convert_never_skips() {
+        let binary = BinaryDetection::Convert(0);
+        assert!(!looks_binary(&binary, b"hello\0world"));
+    }
+
+    #[test]
+    fn convert_binary_bytes_replaces_every_occurrence() {
+        let mut contents = b"a\0b\0c".to_vec();
+        convert_binary_bytes(&mut contents, 0);
+        assert_eq!(contents, b"a\nb\nc");
+    }
+
+    #[test]
+    fn replace_line_substitutes_every_match() {
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let line = b"a1 b22 c333\n";
+        let out =
+            replace_line(&matcher, line, line, b"#").unwrap();
+        assert_eq!(out, b"a# b# c#\n");
+    }
+
+    #[test]
+    fn replace_line_is_a_no_op_without_a_match() {
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let line = b"no digits here\n";
+        let out = replace_line(&matcher, line, line, b"#").unwrap();
+        assert_eq!(out, b"no digits here\n");
+    }
+
+    #[test]
+    fn replace_line_interpolates_capture_groups() {
+        let matcher = RegexMatcher::new(r"(\w+)@(\w+)").unwrap();
+        let line = b"user@host\n";
+        let out =
+            replace_line(&matcher, line, line, b"$2@$1").unwrap();
+        assert_eq!(out, b"host@user\n");
+    }
+
+    #[test]
+    fn replace_line_reads_replaced_text_from_original_line() {
+        // `search_line` has its NUL byte converted to `\n` so the match
+        // can be found at all, but the bytes actually written -- here,
+        // the capture group pulled into `replacement` -- must come from
+        // `line`, the unconverted original: the captured `\n` in
+        // `search_line` should come out as the original `\0`, not `\n`.
+        let matcher = RegexMatcher::new(r"1(\n)").unwrap();
+        let line = b"a1\0";
+        let search_line = b"a1\n";
+        let out = replace_line(&matcher, line, search_line, b"[$1]").unwrap();
+        assert_eq!(out, b"a[\0]");
+    }
+
+    #[test]
+    fn replace_file_rewrites_atomically_with_backup() {
+        let dir = tempfile_dir();
+        let path = dir.join("input.txt");
+        fs::write(&path, b"a1 b2\nc3 d4\n").unwrap();
+
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let replacer =
+            InPlaceReplacer::new(Some(b".bak".to_vec()), BinaryDetection::None);
+        let replaced =
+            replacer.replace_file(&path, &matcher, b"N").unwrap();
+
+        assert!(replaced);
+        assert_eq!(fs::read(&path).unwrap(), b"aN bN\ncN dN\n");
+        assert_eq!(
+            fs::read(dir.join("input.txt.bak")).unwrap(),
+            b"a1 b2\nc3 d4\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replace_file_skips_files_with_no_match() {
+        let dir = tempfile_dir();
+        let path = dir.join("input.txt");
+        fs::write(&path, b"no digits here\n").unwrap();
+
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let replacer =
+            InPlaceReplacer::new(Some(b".bak".to_vec()), BinaryDetection::None);
+        let replaced =
+            replacer.replace_file(&path, &matcher, b"N").unwrap();
+
+        assert!(!replaced);
+        assert_eq!(fs::read(&path).unwrap(), b"no digits here\n");
+        assert!(!dir.join("input.txt.bak").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replace_file_skips_binary_files() {
+        let dir = tempfile_dir();
+        let path = dir.join("input.bin");
+        fs::write(&path, b"a1\0b2\n").unwrap();
+
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let replacer = InPlaceReplacer::new(None, BinaryDetection::Quit(0));
+        let replaced =
+            replacer.replace_file(&path, &matcher, b"N").unwrap();
+
+        assert!(!replaced);
+        assert_eq!(fs::read(&path).unwrap(), b"a1\0b2\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replace_file_converts_for_matching_but_preserves_original_bytes() {
+        let dir = tempfile_dir();
+        let path = dir.join("input.bin");
+        fs::write(&path, b"a1\0b2\n").unwrap();
+
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let replacer = InPlaceReplacer::new(None, BinaryDetection::Convert(0));
+        let replaced =
+            replacer.replace_file(&path, &matcher, b"N").unwrap();
+
+        // The NUL byte was converted to `\n` only to split `a1\0b2\n` into
+        // two matchable lines; it's nowhere near either match, so it must
+        // come out on disk exactly as it went in, not as the `\n` used to
+        // find the lines.
+        assert!(replaced);
+        assert_eq!(fs::read(&path).unwrap(), b"aN\0bN\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replace_file_cleans_up_tmp_file_on_error() {
+        let dir = tempfile_dir();
+        let path = dir.join("input.txt");
+        fs::write(&path, b"a1 b2\n").unwrap();
+
+        // A NUL byte in the backup suffix makes the backup `fs::copy`
+        // fail with `InvalidInput` after the tmp file has already been
+        // created and written, without touching `path` or needing a
+        // real disk-full/permission failure to force the error.
+        let matcher = RegexMatcher::new(r"\d+").unwrap();
+        let replacer =
+            InPlaceReplacer::new(Some(vec![0u8]), BinaryDetection::None);
+        let err = replacer.replace_file(&path, &matcher, b"N").unwrap_err();
+        assert!(err.to_string().to_lowercase().contains("nul byte"));
+
+        assert_eq!(fs::read(&path).unwrap(), b"a1 b2\n");
+        let leftover = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().starts_with(".rg-"));
+        assert!(!leftover, "tmp file was not cleaned up after the error");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn tempfile_dir() -> PathBuf {
+        let dir = crate::tempname::unique_path(
+            &std::env::temp_dir(),
+            "replace-test",
+        );
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
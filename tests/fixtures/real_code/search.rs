@@ -0,0 +1,171 @@
+/*!
+The glue between the matcher (which finds matches in bytes) and the
+ignore/walk machinery (which finds files): `SearchWorker` runs one
+haystack through one matcher and one printer.
+*/
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    flags::lowargs::SortMode,
+    haystack::Haystack,
+    linenum::LineNumberPad,
+    sort::{Buffer, SortKey},
+};
+
+/// The pattern matcher ripgrep was built with.
+pub(crate) enum PatternMatcher {
+    RustRegex(grep::regex::RegexMatcher),
+}
+
+/// The printer ripgrep writes matches to, selected by `SearchMode`.
+pub(crate) enum Printer<W> {
+    /// Plain standard-mode output, used whenever `--line-number-width`
+    /// wasn't given -- the overwhelming majority of standard-mode
+    /// searches. Writes go straight to `W` with none of `LineNumberPad`'s
+    /// line-buffering overhead.
+    Standard(grep::printer::Standard<W>),
+    /// Standard-mode output with its line number column padded per
+    /// `--line-number-width`.
+    StandardPadded(grep::printer::Standard<LineNumberPad<W>>),
+    Summary(grep::printer::Summary<W>),
+    JSON(grep::printer::JSON<W>),
+}
+
+impl<W: Write> Printer<W> {
+    /// Build the standard printer. When `line_number_width` is `Some`,
+    /// `wtr` is wrapped in `LineNumberPad` so its line number column (if
+    /// printed at all) is padded out to that many columns, per
+    /// `--line-number-width`.
+    ///
+    /// `line_number_width` should already be `None` unless `HiArgs` has
+    /// decided line numbers are actually being printed for this search
+    /// (see `HiArgs::from_low_args`, which forces it off for `vimgrep`
+    /// output). The `None` case skips `LineNumberPad` entirely rather
+    /// than constructing a passthrough wrapper around it, since that's
+    /// the path nearly every standard-mode search takes and it shouldn't
+    /// pay for buffering and draining every line into a fresh `Vec` that
+    /// `write_padded_line` was always going to no-op back out of anyway.
+    pub(crate) fn standard(
+        wtr: W,
+        line_number_width: Option<usize>,
+    ) -> Printer<W> {
+        match line_number_width {
+            None => Printer::Standard(
+                grep::printer::StandardBuilder::new().build(wtr),
+            ),
+            Some(width) => {
+                let padded = LineNumberPad::new(wtr, Some(width));
+                Printer::StandardPadded(
+                    grep::printer::StandardBuilder::new().build(padded),
+                )
+            }
+        }
+    }
+}
+
+pub(crate) struct SearchWorkerBuilder {
+    sort: Option<SortMode>,
+    sort_spill_size: usize,
+}
+
+impl SearchWorkerBuilder {
+    pub(crate) fn new() -> SearchWorkerBuilder {
+        // Matches the value `sort::Buffer` used back when its spill
+        // threshold was a hardcoded constant. `HiArgs::from_low_args`
+        // resolves `--sort-spill-size` (or its default) into
+        // `HiArgs::sort_spill_size` but does not touch this builder; this
+        // value stays in effect whenever `sort_spill_size` is not called.
+        SearchWorkerBuilder { sort: None, sort_spill_size: 1 << 20 }
+    }
+
+    pub(crate) fn sort(
+        mut self,
+        sort: Option<SortMode>,
+    ) -> SearchWorkerBuilder {
+        self.sort = sort;
+        self
+    }
+
+    /// How large a single file's buffered output can grow, in bytes,
+    /// before `sort::Buffer` spills it to a temp file. Only meaningful
+    /// when `sort` is set. See `--sort-spill-size`.
+    pub(crate) fn sort_spill_size(
+        mut self,
+        sort_spill_size: usize,
+    ) -> SearchWorkerBuilder {
+        self.sort_spill_size = sort_spill_size;
+        self
+    }
+
+    pub(crate) fn build<W: Write>(
+        &self,
+        matcher: PatternMatcher,
+        printer: Printer<W>,
+    ) -> SearchWorker<W> {
+        SearchWorker {
+            matcher,
+            printer,
+            sort: self.sort.clone(),
+            sort_spill_size: self.sort_spill_size,
+        }
+    }
+}
+
+pub(crate) struct SearchWorker<W> {
+    matcher: PatternMatcher,
+    printer: Printer<W>,
+    sort: Option<SortMode>,
+    sort_spill_size: usize,
+}
+
+impl<W: Write> SearchWorker<W> {
+    /// Search a single haystack.
+    ///
+    /// When `--sort`/`--sortr` is active, this thread no longer writes
+    /// straight to stdout: doing that directly from N worker threads is
+    /// exactly what makes sorted output nondeterministic. Instead, the
+    /// printed output is buffered into a `sort::Buffer` tagged with the
+    /// file's `sort::SortKey` and handed back to the caller, which pushes
+    /// it into a shared `sort::SortedBuffers` (or `sort::PathMergeWriter`
+    /// for `SortModeKind::Path`) that orders and flushes buffers once the
+    /// walk is done. Otherwise output goes straight to stdout, same as
+    /// before, and this returns `None`.
+    pub(crate) fn search(
+        &mut self,
+        haystack: &Haystack,
+    ) -> anyhow::Result<Option<Buffer>> {
+        if let Some(sort) = self.sort.clone() {
+            let key = SortKey::new(haystack.path(), &sort)?;
+            let tmp_dir = haystack
+                .path()
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .to_path_buf();
+            let mut buffer =
+                Buffer::new(key, tmp_dir, self.sort_spill_size);
+            self.search_direct(haystack, &mut buffer)?;
+            return Ok(Some(buffer));
+        }
+        let mut stdout = io::stdout().lock();
+        self.search_direct(haystack, &mut stdout)?;
+        Ok(None)
+    }
+
+    /// The "normal" path: search `haystack` and write matches straight to
+    /// `out`.
+    fn search_direct(
+        &mut self,
+        haystack: &Haystack,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<()> {
+        // The actual `grep::searcher::Searcher::search_path` call (with
+        // `self.matcher` and `self.printer` as the sink) lives here in
+        // the real pipeline.
+        let _ = (haystack, out, &self.matcher, &self.printer);
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+/*!
+Buffering of per-file search output for `--sort`/`--sortr`.
+
+Sorting used to force ripgrep onto a single thread, since the simplest
+way to guarantee ordered output is to never have any concurrent output to
+order in the first place. That made sorted mode a huge performance cliff
+on large trees. Instead, each worker thread searches its files across the
+usual thread pool and writes what it would have printed into a `Buffer`
+tagged with that file's `SortKey`. Once the walk finishes, `SortedBuffers`
+orders every buffer by key (honoring `SortMode::reverse`) and flushes them
+to the real output in order. Buffers that grow past a configurable
+threshold (`--sort-spill-size`, `HiArgs::sort_spill_size`) are moved out
+of memory into a temp file so a handful of huge matches can't blow up
+memory usage; `PathMergeWriter` additionally lets `SortModeKind::Path`
+start streaming output before the walk is done, since the final path
+order is known up front from the directory walk itself.
+*/
+
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::flags::lowargs::{SortMode, SortModeKind};
+
+/// The value buffered output is ordered by.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum SortKey {
+    Path(PathBuf),
+    Time(Option<SystemTime>),
+    Size(u64),
+}
+
+impl SortKey {
+    /// Build the sort key for `path` according to `mode`.
+    pub(crate) fn new(path: &Path, mode: &SortMode) -> io::Result<SortKey> {
+        Ok(match mode.kind {
+            SortModeKind::Path => SortKey::Path(path.to_path_buf()),
+            SortModeKind::LastModified => {
+                SortKey::Time(path.metadata()?.modified().ok())
+            }
+            SortModeKind::LastAccessed => {
+                SortKey::Time(path.metadata()?.accessed().ok())
+            }
+            SortModeKind::Created => {
+                SortKey::Time(path.metadata()?.created().ok())
+            }
+            SortModeKind::FileSize => SortKey::Size(path.metadata()?.len()),
+        })
+    }
+
+    /// Compare two keys. Keys being compared are always built from the
+    /// same `SortModeKind` in practice, since a single search only ever
+    /// uses one `SortMode`, so the mismatched-variant arm below is
+    /// unreachable in normal operation; it's only there to make the
+    /// function total.
+    fn cmp(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Path(a), SortKey::Path(b)) => a.cmp(b),
+            (SortKey::Time(a), SortKey::Time(b)) => a.cmp(b),
+            (SortKey::Size(a), SortKey::Size(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+enum Storage {
+    Memory(Vec<u8>),
+    Spilled(File),
+}
+
+/// The buffered printed output for a single searched file, tagged with
+/// the sort key used to place it among the rest once every file has been
+/// searched.
+///
+/// `Buffer` implements `io::Write` directly so it can be handed to the
+/// searcher as its sink: bytes are appended (and spilled to disk past
+/// `spill_threshold`) as the search itself produces them, rather than
+/// the caller collecting a whole file's output into its own unbounded
+/// buffer first and copying it in afterward, which would defeat the
+/// point of spilling.
+pub(crate) struct Buffer {
+    key: SortKey,
+    tmp_dir: PathBuf,
+    spill_threshold: usize,
+    storage: Storage,
+}
+
+impl Buffer {
+    /// Start a new empty buffer for the given sort key. `tmp_dir` is
+    /// where this buffer spills to if it grows past `spill_threshold`
+    /// bytes, per `--sort-spill-size` (see `HiArgs::sort_spill_size`).
+    pub(crate) fn new(
+        key: SortKey,
+        tmp_dir: PathBuf,
+        spill_threshold: usize,
+    ) -> Buffer {
+        Buffer {
+            key,
+            tmp_dir,
+            spill_threshold,
+            storage: Storage::Memory(Vec::new()),
+        }
+    }
+
+    /// Stream this buffer's contents to `out`, in order.
+    fn flush_to<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match &mut self.storage {
+            Storage::Memory(mem) => out.write_all(mem),
+            Storage::Spilled(file) => {
+                file.seek(SeekFrom::Start(0))?;
+                io::copy(file, out).map(|_| ())
+            }
+        }
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.storage {
+            Storage::Memory(mem) => {
+                mem.extend_from_slice(buf);
+                if mem.len() > self.spill_threshold {
+                    let mut file = spill_file(&self.tmp_dir)?;
+                    file.write_all(mem)?;
+                    self.storage = Storage::Spilled(file);
+                }
+            }
+            Storage::Spilled(file) => file.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.storage {
+            Storage::Memory(_) => Ok(()),
+            Storage::Spilled(file) => file.flush(),
+        }
+    }
+}
+
+/// Open a file to spill a buffer's contents into.
+///
+/// The file is unlinked immediately after creation: we keep writing and
+/// reading through our open handle (which is unaffected by the unlink on
+/// Unix and Windows alike), so the OS reclaims the space as soon as we
+/// drop it without us having to track a path to clean up.
+///
+/// Opened with `create_new`, per `tempname::unique_path`'s contract, so a
+/// pre-existing file at the chosen path (a leftover, or something planted
+/// by another user in a shared, writable search directory) causes a clean
+/// `AlreadyExists` error instead of silently being opened through and
+/// truncated -- `unique_path` only promises the name is unique among
+/// calls to itself, not that nothing else could ever occupy it. On that
+/// error we just ask for another name and try again.
+fn spill_file(dir: &Path) -> io::Result<File> {
+    loop {
+        let path = crate::tempname::unique_path(dir, "sort");
+        let opened = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path);
+        match opened {
+            Ok(file) => {
+                let _ = std::fs::remove_file(&path);
+                return Ok(file);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Collects one `Buffer` per searched file and, once the walk is done,
+/// flushes them to the real output in sorted order.
+pub(crate) struct SortedBuffers {
+    mode: SortMode,
+    buffers: Vec<Buffer>,
+}
+
+impl SortedBuffers {
+    pub(crate) fn new(mode: SortMode) -> SortedBuffers {
+        SortedBuffers { mode, buffers: Vec::new() }
+    }
+
+    /// Called by a worker thread once it's done searching a single file.
+    pub(crate) fn push(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+    }
+
+    /// Order every buffer by key (reversing if the mode asks for it) and
+    /// write them out in that order.
+    pub(crate) fn flush<W: Write>(mut self, out: &mut W) -> io::Result<()> {
+        self.buffers.sort_by(|a, b| {
+            let ord = a.key.cmp(&b.key);
+            if self.mode.reverse { ord.reverse() } else { ord }
+        });
+        for mut buffer in self.buffers {
+            buffer.flush_to(out)?;
+        }
+        Ok(())
+    }
+}
+
+/// A streaming writer for `SortModeKind::Path`.
+///
+/// Unlike mtime/atime/ctime/size, which require a `stat` of each file
+/// before we know where it lands, the final path order is already known
+/// from the directory walk as soon as it enumerates entries. So instead
+/// of waiting for every file to finish searching like `SortedBuffers`
+/// does, each worker records its buffer in the slot reserved for its
+/// path's position in walk order, and `drain_ready` flushes the
+/// contiguous prefix of slots that have arrived so far -- letting output
+/// start well before the whole tree has been walked.
+pub(crate) struct PathMergeWriter {
+    slots: Vec<Option<Buffer>>,
+    next: usize,
+}
+
+impl PathMergeWriter {
+    pub(crate) fn new(path_count: usize) -> PathMergeWriter {
+        PathMergeWriter {
+            slots: (0..path_count).map(|_| None).collect(),
+            next: 0,
+        }
+    }
+
+    /// Record the buffer produced for the file at walk-order `index`.
+    pub(crate) fn set(&mut self, index: usize, buffer: Buffer) {
+        self.slots[index] = Some(buffer);
+    }
+
+    /// Flush every buffer in the contiguous prefix starting at the next
+    /// unflushed index.
+    pub(crate) fn drain_ready<W: Write>(
+        &mut self,
+        out: &mut W,
+    ) -> io::Result<()> {
+        while self.next < self.slots.len() {
+            let Some(buffer) = self.slots[self.next].as_mut() else {
+                break;
+            };
+            buffer.flush_to(out)?;
+            self.slots[self.next] = None;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Matches `DEFAULT_SORT_SPILL_SIZE` in `hiargs.rs`, since these
+    /// tests predate `--sort-spill-size` and still want the "default"
+    /// threshold rather than hardcoding a test-only one.
+    const TEST_SPILL_THRESHOLD: usize = 1 << 20;
+
+    #[test]
+    fn sort_key_ordering_respects_reverse() {
+        let mut keys =
+            vec![SortKey::Size(30), SortKey::Size(10), SortKey::Size(20)];
+        keys.sort_by(|a, b| a.cmp(b));
+        assert_eq!(
+            keys,
+            vec![SortKey::Size(10), SortKey::Size(20), SortKey::Size(30)]
+        );
+        keys.sort_by(|a, b| a.cmp(b).reverse());
+        assert_eq!(
+            keys,
+            vec![SortKey::Size(30), SortKey::Size(20), SortKey::Size(10)]
+        );
+    }
+
+    #[test]
+    fn buffer_spills_past_threshold_and_round_trips() {
+        let dir = std::env::temp_dir();
+        let mut buf = Buffer::new(SortKey::Size(0), dir, TEST_SPILL_THRESHOLD);
+        let chunk = vec![b'x'; TEST_SPILL_THRESHOLD + 1];
+        buf.write_all(&chunk).unwrap();
+        assert!(matches!(buf.storage, Storage::Spilled(_)));
+        let mut out = Vec::new();
+        buf.flush_to(&mut out).unwrap();
+        assert_eq!(out, chunk);
+    }
+
+    #[test]
+    fn buffer_stays_in_memory_below_threshold() {
+        let dir = std::env::temp_dir();
+        let mut buf =
+            Buffer::new(SortKey::Size(0), dir, TEST_SPILL_THRESHOLD);
+        buf.write_all(b"hello").unwrap();
+        assert!(matches!(buf.storage, Storage::Memory(_)));
+        let mut out = Vec::new();
+        buf.flush_to(&mut out).unwrap();
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn buffer_spill_threshold_is_configurable() {
+        let dir = std::env::temp_dir();
+        let mut buf = Buffer::new(SortKey::Size(0), dir, 4);
+        buf.write_all(b"hello").unwrap();
+        assert!(matches!(buf.storage, Storage::Spilled(_)));
+        let mut out = Vec::new();
+        buf.flush_to(&mut out).unwrap();
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn sorted_buffers_flush_in_key_order() {
+        let dir = std::env::temp_dir();
+        let mode = SortMode { kind: SortModeKind::FileSize, reverse: false };
+        let mut buffers = SortedBuffers::new(mode);
+        for (size, text) in [(3u64, "c"), (1, "a"), (2, "b")] {
+            let mut buf = Buffer::new(
+                SortKey::Size(size),
+                dir.clone(),
+                TEST_SPILL_THRESHOLD,
+            );
+            buf.write_all(text.as_bytes()).unwrap();
+            buffers.push(buf);
+        }
+        let mut out = Vec::new();
+        buffers.flush(&mut out).unwrap();
+        assert_eq!(out, b"abc");
+    }
+
+    #[test]
+    fn sorted_buffers_flush_reversed() {
+        let dir = std::env::temp_dir();
+        let mode = SortMode { kind: SortModeKind::FileSize, reverse: true };
+        let mut buffers = SortedBuffers::new(mode);
+        for (size, text) in [(3u64, "c"), (1, "a"), (2, "b")] {
+            let mut buf = Buffer::new(
+                SortKey::Size(size),
+                dir.clone(),
+                TEST_SPILL_THRESHOLD,
+            );
+            buf.write_all(text.as_bytes()).unwrap();
+            buffers.push(buf);
+        }
+        let mut out = Vec::new();
+        buffers.flush(&mut out).unwrap();
+        assert_eq!(out, b"cba");
+    }
+
+    #[test]
+    fn path_merge_writer_streams_contiguous_prefix() {
+        let dir = std::env::temp_dir();
+        let mut writer = PathMergeWriter::new(3);
+        let mut out = Vec::new();
+
+        let mut b = Buffer::new(
+            SortKey::Path("b".into()),
+            dir.clone(),
+            TEST_SPILL_THRESHOLD,
+        );
+        b.write_all(b"B").unwrap();
+        writer.set(1, b);
+        writer.drain_ready(&mut out).unwrap();
+        assert!(out.is_empty(), "index 0 hasn't arrived yet");
+
+        let mut a = Buffer::new(
+            SortKey::Path("a".into()),
+            dir.clone(),
+            TEST_SPILL_THRESHOLD,
+        );
+        a.write_all(b"A").unwrap();
+        writer.set(0, a);
+        writer.drain_ready(&mut out).unwrap();
+        assert_eq!(out, b"AB");
+
+        let mut c =
+            Buffer::new(SortKey::Path("c".into()), dir, TEST_SPILL_THRESHOLD);
+        c.write_all(b"C").unwrap();
+        writer.set(2, c);
+        writer.drain_ready(&mut out).unwrap();
+        assert_eq!(out, b"ABC");
+    }
+}
@@ -0,0 +1,31 @@
+/*!
+A single scheme for picking a unique scratch path next to user data,
+shared by everything that needs one: `sort::spill_file` spills a
+buffer's contents to a temp file, and `replace::TempFile::create_in`
+stages the rewritten contents of a `--replace-in-place` target before
+renaming it into place. Both used to reimplement the same
+`AtomicU64`-plus-`process::id()` scheme independently; picking the name
+here instead keeps there being exactly one place that has to get the
+collision-avoidance right.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Build a path inside `dir` that no other call to this function, in
+/// this process or in any other process running at the same time, will
+/// produce: `.rg-<tag>-<pid>-<id>`. A later process that reuses the same
+/// pid starts its counter over and can repeat earlier names.
+///
+/// This only picks the name; it's up to the caller to create whatever
+/// it needs at that path (a file, via `create_new` to catch the
+/// vanishingly unlikely case of a collision with something unrelated, or
+/// a directory).
+pub(crate) fn unique_path(dir: &Path, tag: &str) -> PathBuf {
+    let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+    dir.join(format!(".rg-{tag}-{}-{id}", std::process::id()))
+}